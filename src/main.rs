@@ -1,22 +1,104 @@
 use cached::proc_macro::cached;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::thread;
 
 use clap::{App, AppSettings, Arg};
 
 pub mod stm;
 
+struct Worker {
+    name: String,
+    status: Option<i32>,
+    completed: bool,
+}
+
 #[cached]
-fn load_config() -> stm::Config {
-    stm::Config::default().expect("error while loading config")
+fn load_config(config_path: Option<PathBuf>) -> stm::Config {
+    match config_path {
+        Some(path) => stm::Config::from_file(path),
+        None => stm::Config::load_merged(),
+    }
+    .expect("error while loading config")
+}
+
+// Scans the raw process args for `--config`/`--config=<path>` ahead of
+// clap parsing so per-manager validators can honor the override too.
+fn resolve_config_path(args: &[String]) -> Option<PathBuf> {
+    for (i, arg) in args.iter().enumerate() {
+        if let Some(value) = arg.strip_prefix("--config=") {
+            return Some(PathBuf::from(value));
+        }
+        if arg == "--config" {
+            return args.get(i + 1).map(PathBuf::from);
+        }
+    }
+    None
 }
 
-fn has_valid_manager(v: String) -> Result<(), String> {
-    let config = load_config();
+fn has_valid_manager(config_path: Option<PathBuf>, v: String) -> Result<(), String> {
+    let config = load_config(config_path);
     let valid_managers = config.managers.names();
 
-    if !&valid_managers.contains(&v) {
-        return Err(format!("invalid manager {}", v));
+    if valid_managers.contains(&&v) || config.aliases.contains_key(&v) {
+        return Ok(());
+    }
+
+    Err(format!("invalid manager {}", v))
+}
+
+// Expands alias names (e.g. "desktop" -> ["arch", "cargo"]) into the manager
+// names they stand for, recursively resolving nested aliases. Bails with an
+// error if an alias references itself transitively.
+fn expand_aliases(config: &stm::Config, names: Vec<String>) -> Result<Vec<String>, String> {
+    let mut resolved = Vec::new();
+    for name in names {
+        expand_alias(config, &name, &mut Vec::new(), &mut resolved)?;
     }
 
+    // Overlapping aliases (or an alias plus an explicitly-named manager it
+    // already expands to) can yield the same manager more than once, which
+    // would otherwise run its install/update command twice in parallel.
+    let mut seen = std::collections::HashSet::new();
+    resolved.retain(|name| seen.insert(name.clone()));
+
+    Ok(resolved)
+}
+
+// Expands aliases into manager names, printing a clean error and exiting
+// (matching the validator's error format) instead of panicking on a bad
+// alias member.
+fn resolve_managers(config: &stm::Config, names: Vec<String>) -> Vec<String> {
+    expand_aliases(config, names).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    })
+}
+
+fn expand_alias(
+    config: &stm::Config,
+    name: &str,
+    seen: &mut Vec<String>,
+    resolved: &mut Vec<String>,
+) -> Result<(), String> {
+    match config.aliases.get(name) {
+        Some(members) => {
+            if seen.contains(&name.to_string()) {
+                return Err(format!("alias cycle detected at \"{}\"", name));
+            }
+            seen.push(name.to_string());
+            for member in members {
+                expand_alias(config, member, seen, resolved)?;
+            }
+            seen.pop();
+        }
+        None => {
+            if config.find_manager(name).is_none() {
+                return Err(format!("invalid manager {}", name));
+            }
+            resolved.push(name.to_string());
+        }
+    }
     Ok(())
 }
 
@@ -24,11 +106,32 @@ fn main() {
     std::env::set_var("STM_CONFIG_PATH", stm::app_dir());
     std::fs::create_dir_all(stm::app_dir()).expect("error while creating app dir");
 
+    let args: Vec<String> = std::env::args().collect();
+    let config_path = resolve_config_path(&args);
+    let validate_manager = {
+        let config_path = config_path.clone();
+        move |v: String| has_valid_manager(config_path.clone(), v)
+    };
+
     let matches = App::new("System Tool Manager")
         .about("System Tool Manager (STM) is a tool for install and updates any system tools in a easy way.")
         .author("Emerson Max de Medeiros Silva <emersonmx@gmail.com>")
         .version("1.0.0")
         .setting(AppSettings::ArgRequiredElseHelp)
+        .arg(
+            Arg::with_name("config")
+                .long("config")
+                .value_name("PATH")
+                .help("use this config file instead of the default lookup")
+                .takes_value(true)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("dry-run")
+                .long("dry-run")
+                .help("print the rendered install/update command instead of running it")
+                .global(true),
+        )
         .subcommand(
             App::new("install").about("Run managers install").arg(
                 Arg::with_name("managers")
@@ -36,7 +139,7 @@ fn main() {
                     .index(1)
                     .required(true)
                     .multiple(true)
-                    .validator(has_valid_manager)
+                    .validator(validate_manager.clone())
             ),
         )
         .subcommand(
@@ -46,12 +149,25 @@ fn main() {
                     .index(1)
                     .required(true)
                     .multiple(true)
-                    .validator(has_valid_manager)
+                    .validator(validate_manager.clone())
             ),
         )
+        .subcommand(
+            App::new("status")
+                .about("Show installed/missing status for managers' tools")
+                .arg(
+                    Arg::with_name("managers")
+                        .help("the managers to show status for (default: all)")
+                        .index(1)
+                        .multiple(true)
+                        .validator(validate_manager)
+                ),
+        )
         .subcommand(App::new("list").about("List all available managers"))
         .get_matches();
 
+    let dry_run = matches.is_present("dry-run");
+
     match matches.subcommand() {
         ("install", Some(install_matches)) => {
             let args: Vec<String> = install_matches
@@ -59,7 +175,7 @@ fn main() {
                 .unwrap()
                 .map(|m| m.to_string())
                 .collect();
-            install_command(args);
+            install_command(config_path, dry_run, args);
         }
         ("update", Some(update_matches)) => {
             let args: Vec<String> = update_matches
@@ -67,54 +183,192 @@ fn main() {
                 .unwrap()
                 .map(|m| m.to_string())
                 .collect();
-            update_command(args);
+            update_command(config_path, dry_run, args);
+        }
+        ("status", Some(status_matches)) => {
+            let args: Vec<String> = match status_matches.values_of("managers") {
+                Some(values) => values.map(|m| m.to_string()).collect(),
+                None => load_config(config_path.clone())
+                    .managers
+                    .names()
+                    .into_iter()
+                    .cloned()
+                    .collect(),
+            };
+            status_command(config_path, args);
         }
         ("list", Some(_)) => {
-            list_command();
+            list_command(config_path);
         }
         _ => {}
     }
 }
 
-fn install_command(managers: Vec<String>) {
-    let config = load_config();
+fn install_command(config_path: Option<PathBuf>, dry_run: bool, managers: Vec<String>) {
+    let config = load_config(config_path);
+    let managers = resolve_managers(&config, managers);
 
-    managers
+    let jobs: Vec<(stm::Manager, Vec<String>)> = managers
         .iter()
         .map(|m| config.find_manager(&m).unwrap())
-        .for_each(|m| {
-            let packages: Vec<&str> = config
+        .filter(|m| m.applies_to_current_os())
+        .map(|m| {
+            let packages = config
                 .tools
                 .filter_by_manager(&m.name)
                 .into_iter()
                 .filter(|t| !t.is_installed())
-                .map(|t| t.package.as_str())
+                .map(|t| t.package.clone())
                 .collect();
-            m.install_packages(packages)
-                .expect("failed to execute process");
-        });
+            (m.clone(), packages)
+        })
+        .collect();
+
+    if dry_run {
+        print_dry_run(jobs, stm::Manager::render_install_command);
+        return;
+    }
+
+    let failed = run_workers(jobs, |m, packages| {
+        let packages: Vec<&str> = packages.iter().map(String::as_str).collect();
+        m.install_packages(packages)
+    });
+
+    if failed {
+        std::process::exit(1);
+    }
 }
 
-fn update_command(managers: Vec<String>) {
-    let config = load_config();
+fn update_command(config_path: Option<PathBuf>, dry_run: bool, managers: Vec<String>) {
+    let config = load_config(config_path);
+    let managers = resolve_managers(&config, managers);
 
-    managers
+    let jobs: Vec<(stm::Manager, Vec<String>)> = managers
         .iter()
         .map(|m| config.find_manager(&m).unwrap())
-        .for_each(|m| {
-            let packages: Vec<&str> = config
+        .filter(|m| m.applies_to_current_os())
+        .map(|m| {
+            let packages = config
                 .tools
                 .filter_by_manager(&m.name)
                 .into_iter()
-                .map(|t| t.package.as_str())
+                .map(|t| t.package.clone())
                 .collect();
-            m.update_packages(packages)
-                .expect("failed to execute process");
+            (m.clone(), packages)
+        })
+        .collect();
+
+    if dry_run {
+        print_dry_run(jobs, stm::Manager::render_update_command);
+        return;
+    }
+
+    let failed = run_workers(jobs, |m, packages| {
+        let packages: Vec<&str> = packages.iter().map(String::as_str).collect();
+        m.update_packages(packages)
+    });
+
+    if failed {
+        std::process::exit(1);
+    }
+}
+
+// Prints the fully-rendered command each job would run, without running it.
+fn print_dry_run(
+    jobs: Vec<(stm::Manager, Vec<String>)>,
+    render: fn(&stm::Manager, &[&str]) -> String,
+) {
+    for (manager, packages) in jobs {
+        let packages: Vec<&str> = packages.iter().map(String::as_str).collect();
+        println!("{}: {}", manager.name, render(&manager, &packages));
+    }
+}
+
+// Runs one worker thread per manager, streaming Worker updates back over an
+// mpsc channel so progress can be reported as each manager starts/finishes.
+// Returns true if any worker failed.
+fn run_workers(
+    jobs: Vec<(stm::Manager, Vec<String>)>,
+    run: fn(&stm::Manager, &Vec<String>) -> std::io::Result<std::process::ExitStatus>,
+) -> bool {
+    let (tx, rx) = mpsc::channel();
+
+    let handles: Vec<_> = jobs
+        .into_iter()
+        .map(|(manager, packages)| {
+            let tx = tx.clone();
+            thread::spawn(move || {
+                let name = manager.name.clone();
+                tx.send(Worker {
+                    name: name.clone(),
+                    status: None,
+                    completed: false,
+                })
+                .unwrap();
+
+                let status = run(&manager, &packages).ok().and_then(|s| s.code());
+                tx.send(Worker {
+                    name,
+                    status,
+                    completed: true,
+                })
+                .unwrap();
+            })
+        })
+        .collect();
+    drop(tx);
+
+    let mut failed = false;
+    for worker in rx {
+        if !worker.completed {
+            println!("{}: running", worker.name);
+            continue;
+        }
+
+        match worker.status {
+            Some(0) => println!("{}: done", worker.name),
+            Some(code) => {
+                println!("{}: failed (exit code {})", worker.name, code);
+                failed = true;
+            }
+            None => {
+                println!("{}: failed", worker.name);
+                failed = true;
+            }
+        }
+    }
+
+    for handle in handles {
+        handle.join().expect("worker thread panicked");
+    }
+
+    failed
+}
+
+fn status_command(config_path: Option<PathBuf>, managers: Vec<String>) {
+    let config = load_config(config_path);
+    let managers = resolve_managers(&config, managers);
+
+    managers
+        .iter()
+        .map(|m| config.find_manager(&m).unwrap())
+        .filter(|m| m.applies_to_current_os())
+        .for_each(|m| {
+            println!("{}:", m.name);
+
+            let tools = config.tools.filter_by_manager(&m.name);
+            let installed = tools.iter().filter(|t| t.is_installed()).count();
+            for tool in &tools {
+                let state = if tool.is_installed() { "installed" } else { "missing" };
+                println!("  {} [{}]", tool.package, state);
+            }
+
+            println!("  {}/{} installed", installed, tools.len());
         });
 }
 
-fn list_command() {
-    let config = load_config();
+fn list_command(config_path: Option<PathBuf>) {
+    let config = load_config(config_path);
 
     config
         .managers
@@ -122,3 +376,87 @@ fn list_command() {
         .iter()
         .for_each(|m| println!("{}", m));
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_config() -> stm::Config {
+        let json = r#"{
+            "managers": [
+                {"name": "arch", "install_command": "yay -Sy {{packages}}", "update_command": "yay -Syu"},
+                {"name": "cargo", "install_command": "cargo install {{packages}}", "update_command": "cargo install {{packages}}"}
+            ],
+            "tools": [],
+            "aliases": {
+                "desktop": ["arch", "cargo"],
+                "typo": ["arch", "contirb"],
+                "cycle_a": ["cycle_b"],
+                "cycle_b": ["cycle_a"]
+            }
+        }"#;
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn it_resolves_config_path_from_equals_form() {
+        let args = vec!["stm".to_string(), "--config=/tmp/x.toml".to_string()];
+        let want = Some(PathBuf::from("/tmp/x.toml"));
+        assert_eq!(want, resolve_config_path(&args));
+    }
+
+    #[test]
+    fn it_resolves_config_path_from_space_separated_form() {
+        let args = vec![
+            "stm".to_string(),
+            "--config".to_string(),
+            "/tmp/x.toml".to_string(),
+        ];
+        let want = Some(PathBuf::from("/tmp/x.toml"));
+        assert_eq!(want, resolve_config_path(&args));
+    }
+
+    #[test]
+    fn it_resolves_config_path_to_none_without_an_override() {
+        let args = vec!["stm".to_string(), "install".to_string(), "arch".to_string()];
+        assert_eq!(None, resolve_config_path(&args));
+    }
+
+    #[test]
+    fn it_expands_aliases_into_manager_names() {
+        let config = test_config();
+
+        let want = vec!["arch".to_string(), "cargo".to_string()];
+        let got = expand_aliases(&config, vec!["desktop".to_string()]).unwrap();
+        assert_eq!(want, got);
+    }
+
+    #[test]
+    fn it_dedups_managers_from_overlapping_aliases() {
+        let config = test_config();
+
+        let want = vec!["arch".to_string(), "cargo".to_string()];
+        let got = expand_aliases(
+            &config,
+            vec!["desktop".to_string(), "arch".to_string()],
+        )
+        .unwrap();
+        assert_eq!(want, got);
+    }
+
+    #[test]
+    fn it_rejects_an_alias_member_that_is_not_a_real_manager() {
+        let config = test_config();
+
+        let err = expand_aliases(&config, vec!["typo".to_string()]).unwrap_err();
+        assert_eq!("invalid manager contirb", err);
+    }
+
+    #[test]
+    fn it_detects_alias_cycles() {
+        let config = test_config();
+
+        let err = expand_aliases(&config, vec!["cycle_a".to_string()]).unwrap_err();
+        assert!(err.contains("alias cycle detected"));
+    }
+}
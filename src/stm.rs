@@ -1,16 +1,31 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::error::Error;
 use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus};
 use std::{fs::File, io::BufReader};
 
 pub fn app_dir() -> PathBuf {
     dirs::config_dir().unwrap().join("stm")
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Debug)]
+pub trait Merge {
+    fn merge(&mut self, other: Self);
+}
+
+/// Note: reading a hand-authored `config.toml` (via [`Config::from_file`] or
+/// `toml::from_str`) is fully supported, but serializing a `Config` back out
+/// with `toml::to_string` is not guaranteed to work in general — the `toml`
+/// crate requires table-like fields to be emitted in a consistent order, and
+/// a mix of empty/non-empty `managers`/`tools` can violate that. Config files
+/// are meant to be written by hand, not generated by this program, so only
+/// the read path is exercised.
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub struct Config {
     pub managers: ManagerList,
     pub tools: ToolList,
+    #[serde(default)]
+    pub aliases: HashMap<String, Vec<String>>,
 }
 
 impl Config {
@@ -18,17 +33,63 @@ impl Config {
         app_dir().join("config.json")
     }
 
+    fn toml_path() -> PathBuf {
+        app_dir().join("config.toml")
+    }
+
+    fn fragments_dir() -> PathBuf {
+        app_dir().join("config.d")
+    }
+
+    /// Dispatches on the file extension: `.toml`/`.tml` is parsed as TOML,
+    /// everything else (notably `.json`) as JSON.
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Config, Box<dyn Error>> {
-        let file = File::open(path)?;
-        let reader = BufReader::new(file);
-        let config = serde_json::from_reader(reader)?;
-        Ok(config)
+        let path = path.as_ref();
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") | Some("tml") => {
+                let contents = std::fs::read_to_string(path)?;
+                let config = toml::from_str(&contents)?;
+                Ok(config)
+            }
+            _ => {
+                let file = File::open(path)?;
+                let reader = BufReader::new(file);
+                let config = serde_json::from_reader(reader)?;
+                Ok(config)
+            }
+        }
     }
 
+    /// Prefers `config.toml` over `config.json` in `app_dir()`.
     pub fn default() -> Result<Config, Box<dyn Error>> {
-        let file = File::open(Config::path())?;
-        let reader = BufReader::new(file);
-        let config = serde_json::from_reader(reader)?;
+        let toml_path = Config::toml_path();
+        if toml_path.is_file() {
+            return Config::from_file(toml_path);
+        }
+        Config::from_file(Config::path())
+    }
+
+    /// Loads the base config (see [`Config::default`]) and folds in every `*.json` fragment
+    /// under `config.d/`, in sorted filename order, for deterministic merges.
+    pub fn load_merged() -> Result<Config, Box<dyn Error>> {
+        let mut config = Config::default()?;
+
+        let fragments_dir = Config::fragments_dir();
+        if !fragments_dir.is_dir() {
+            return Ok(config);
+        }
+
+        let mut fragment_paths: Vec<PathBuf> = std::fs::read_dir(&fragments_dir)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().map_or(false, |ext| ext == "json"))
+            .collect();
+        fragment_paths.sort();
+
+        for path in fragment_paths {
+            config.merge(Config::from_file(path)?);
+        }
+
         Ok(config)
     }
 
@@ -37,14 +98,64 @@ impl Config {
     }
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Debug)]
+impl Merge for Config {
+    fn merge(&mut self, other: Self) {
+        self.managers.merge(other.managers);
+        self.tools.merge(other.tools);
+        self.aliases.extend(other.aliases);
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum Os {
+    Linux,
+    Macos,
+    Windows,
+    Unknown,
+}
+
+impl Os {
+    pub fn current() -> Self {
+        match std::env::consts::OS {
+            "linux" => Os::Linux,
+            "macos" => Os::Macos,
+            "windows" => Os::Windows,
+            _ => Os::Unknown,
+        }
+    }
+
+    /// True if `os` is absent (applies to any OS) or matches the current OS.
+    /// Shared by `Manager`/`Tool`'s `applies_to_current_os`.
+    fn matches_current(os: Option<Self>) -> bool {
+        os.is_none_or(|os| os == Self::current())
+    }
+}
+
+impl std::str::FromStr for Os {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "linux" => Ok(Os::Linux),
+            "macos" => Ok(Os::Macos),
+            "windows" => Ok(Os::Windows),
+            "unknown" => Ok(Os::Unknown),
+            _ => Err(format!("invalid os {}", s)),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub struct Manager {
     pub name: String,
     pub install_command: String,
     pub update_command: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub os: Option<Os>,
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub struct ManagerList(Vec<Manager>);
 
 impl ManagerList {
@@ -53,30 +164,99 @@ impl ManagerList {
     }
 }
 
+impl Merge for ManagerList {
+    /// Later fragments override an earlier manager with the same `name`.
+    fn merge(&mut self, other: Self) {
+        for manager in other.0 {
+            match self.0.iter_mut().find(|m| m.name == manager.name) {
+                Some(existing) => *existing = manager,
+                None => self.0.push(manager),
+            }
+        }
+    }
+}
+
 impl Manager {
     pub fn new(name: &str, install_command: &str, update_command: &str) -> Self {
         Self {
             name: String::from(name),
             install_command: String::from(install_command),
             update_command: String::from(update_command),
+            os: None,
         }
     }
+
+    pub fn install_packages(&self, packages: Vec<&str>) -> std::io::Result<ExitStatus> {
+        self.run(&self.install_command, &packages)
+    }
+
+    pub fn update_packages(&self, packages: Vec<&str>) -> std::io::Result<ExitStatus> {
+        self.run(&self.update_command, &packages)
+    }
+
+    /// Renders `install_command` with `{{packages}}` substituted, without
+    /// running it. Used to preview what `install_packages` would execute.
+    pub fn render_install_command(&self, packages: &[&str]) -> String {
+        Self::render(&self.install_command, packages)
+    }
+
+    /// Renders `update_command` with `{{packages}}` substituted, without
+    /// running it. Used to preview what `update_packages` would execute.
+    pub fn render_update_command(&self, packages: &[&str]) -> String {
+        Self::render(&self.update_command, packages)
+    }
+
+    fn render(command: &str, packages: &[&str]) -> String {
+        command.replace("{{packages}}", &packages.join(" "))
+    }
+
+    fn run(&self, command: &str, packages: &[&str]) -> std::io::Result<ExitStatus> {
+        let rendered = Self::render(command, packages);
+        Command::new("sh").arg("-c").arg(rendered).status()
+    }
+
+    /// True if this manager has no `os` restriction or it matches [`Os::current`].
+    pub fn applies_to_current_os(&self) -> bool {
+        Os::matches_current(self.os)
+    }
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub struct Tool {
     pub package: String,
     pub binary: Option<String>,
     pub path: Option<String>,
     pub manager: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub os: Option<Os>,
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Debug)]
+#[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
 pub struct ToolList(Vec<Tool>);
 
 impl ToolList {
     pub fn filter_by_manager(&self, manager: &str) -> Vec<&Tool> {
-        self.0.iter().filter(|t| manager == t.manager).collect()
+        self.0
+            .iter()
+            .filter(|t| manager == t.manager)
+            .filter(|t| t.applies_to_current_os())
+            .collect()
+    }
+}
+
+impl Merge for ToolList {
+    /// Appends tools from `other`, skipping any `(package, manager)` pair
+    /// already present.
+    fn merge(&mut self, other: Self) {
+        for tool in other.0 {
+            let duplicate = self
+                .0
+                .iter()
+                .any(|t| t.package == tool.package && t.manager == tool.manager);
+            if !duplicate {
+                self.0.push(tool);
+            }
+        }
     }
 }
 
@@ -87,6 +267,7 @@ impl Tool {
             binary: Some(String::from(binary.unwrap_or(""))),
             path: Some(String::from(path.unwrap_or(""))),
             manager: String::from(manager),
+            os: None,
         }
     }
 
@@ -97,13 +278,68 @@ impl Tool {
     pub fn new_path(package: &str, path: &str, manager: &str) -> Self {
         Self::new(package, None, Some(path), manager)
     }
+
+    pub fn is_installed(&self) -> bool {
+        if let Some(binary) = self.binary.as_deref().filter(|b| !b.is_empty()) {
+            return is_on_path(binary);
+        }
+        if let Some(path) = self.path.as_deref().filter(|p| !p.is_empty()) {
+            return Path::new(&expand_env_vars(path)).exists();
+        }
+        false
+    }
+
+    /// True if this tool has no `os` restriction or it matches [`Os::current`].
+    pub fn applies_to_current_os(&self) -> bool {
+        Os::matches_current(self.os)
+    }
+}
+
+fn is_on_path(binary: &str) -> bool {
+    std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).any(|dir| dir.join(binary).is_file()))
+        .unwrap_or(false)
+}
+
+fn expand_env_vars(path: &str) -> String {
+    let mut expanded = String::new();
+    let mut chars = path.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            expanded.push(c);
+            continue;
+        }
+
+        let mut name = String::new();
+        if chars.peek() == Some(&'{') {
+            chars.next();
+            for c in chars.by_ref() {
+                if c == '}' {
+                    break;
+                }
+                name.push(c);
+            }
+        } else {
+            while let Some(&c) = chars.peek() {
+                if !c.is_alphanumeric() && c != '_' {
+                    break;
+                }
+                name.push(c);
+                chars.next();
+            }
+        }
+        expanded.push_str(&std::env::var(&name).unwrap_or_default());
+    }
+
+    expanded
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    fn create_temp_json() -> tempfile::NamedTempFile {
+    fn sample_config() -> Config {
         let managers = ManagerList(vec![
             Manager::new("arch", "yay -Sy {{packages}}", "yay -Syu"),
             Manager::new(
@@ -122,7 +358,15 @@ mod tests {
             ),
             Tool::new_path("cargo-watch", "$CARGO_HOME/bin/cargo-watch", "cargo"),
         ]);
-        let config = Config { managers, tools };
+        Config {
+            managers,
+            tools,
+            aliases: HashMap::new(),
+        }
+    }
+
+    fn create_temp_json() -> tempfile::NamedTempFile {
+        let config = sample_config();
         let tf = tempfile::NamedTempFile::new().unwrap();
         let writer = std::io::BufWriter::new(&tf);
         serde_json::to_writer(writer, &config).unwrap();
@@ -130,6 +374,19 @@ mod tests {
         tf
     }
 
+    // Config files are hand-authored, not round-tripped through
+    // `toml::to_string` (see the note on `Config`), so fixtures are written
+    // as literal TOML text instead of serializing a `Config` value.
+    fn create_temp_toml(contents: &str) -> tempfile::NamedTempFile {
+        let tf = tempfile::Builder::new()
+            .suffix(".toml")
+            .tempfile()
+            .unwrap();
+        std::fs::write(tf.path(), contents).unwrap();
+
+        tf
+    }
+
     #[test]
     fn it_has_app_dir() {
         let wants = dirs::config_dir().unwrap().join("stm");
@@ -152,6 +409,45 @@ mod tests {
         assert_eq!(want, Config::from_file(p).unwrap());
     }
 
+    #[test]
+    fn it_loads_from_toml() {
+        let contents = r#"
+[[managers]]
+name = "arch"
+install_command = "yay -Sy {{packages}}"
+update_command = "yay -Syu"
+
+[[tools]]
+package = "alacritty"
+binary = "alacritty"
+manager = "arch"
+"#;
+        let tf = create_temp_toml(contents);
+        let p = tf.path();
+
+        let want: Config = toml::from_str(contents).unwrap();
+        assert_eq!(want, Config::from_file(p).unwrap());
+    }
+
+    #[test]
+    fn it_loads_from_toml_with_an_empty_tools_list() {
+        let contents = r#"
+tools = []
+
+[[managers]]
+name = "arch"
+install_command = "yay -Sy {{packages}}"
+update_command = "yay -Syu"
+"#;
+        let tf = create_temp_toml(contents);
+        let p = tf.path();
+
+        let want: Config = toml::from_str(contents).unwrap();
+        assert_eq!(want, Config::from_file(p).unwrap());
+        assert_eq!(vec!["arch"], want.managers.names());
+        assert!(want.tools.filter_by_manager("arch").is_empty());
+    }
+
     #[test]
     fn it_has_a_manager_list_with_names() {
         let tf = create_temp_json();
@@ -178,6 +474,76 @@ mod tests {
         assert_eq!(want, c.find_manager("rust"));
     }
 
+    #[test]
+    fn it_parses_os_from_str() {
+        use std::str::FromStr;
+
+        assert_eq!(Os::Linux, Os::from_str("linux").unwrap());
+        assert_eq!(Os::Macos, Os::from_str("macos").unwrap());
+        assert_eq!(Os::Windows, Os::from_str("windows").unwrap());
+        assert!(Os::from_str("plan9").is_err());
+    }
+
+    #[test]
+    fn it_filters_tools_by_manager_and_os() {
+        let mut tool = Tool::new_binary("yay-only-tool", "yay", "arch");
+        tool.os = Some(Os::current());
+        let other_os = match Os::current() {
+            Os::Linux => Os::Macos,
+            _ => Os::Linux,
+        };
+        let mut unmatched = Tool::new_binary("other-os-tool", "other", "arch");
+        unmatched.os = Some(other_os);
+
+        let tools = ToolList(vec![tool, unmatched]);
+
+        let want = vec!["yay-only-tool"];
+        let got: Vec<&str> = tools
+            .filter_by_manager("arch")
+            .iter()
+            .map(|t| t.package.as_str())
+            .collect();
+        assert_eq!(want, got);
+    }
+
+    #[test]
+    fn it_merges_manager_lists_by_name() {
+        let mut managers = ManagerList(vec![Manager::new(
+            "arch",
+            "yay -Sy {{packages}}",
+            "yay -Syu",
+        )]);
+        let other = ManagerList(vec![
+            Manager::new("arch", "paru -Sy {{packages}}", "paru -Syu"),
+            Manager::new(
+                "cargo",
+                "cargo install {{packages}}",
+                "cargo install {{packages}}",
+            ),
+        ]);
+
+        managers.merge(other);
+
+        let want = vec!["arch", "cargo"];
+        assert_eq!(want, managers.names());
+        assert_eq!("paru -Sy {{packages}}", managers.0[0].install_command);
+    }
+
+    #[test]
+    fn it_merges_tool_lists_deduplicating_on_package_and_manager() {
+        let mut tools = ToolList(vec![Tool::new_binary("alacritty", "alacritty", "arch")]);
+        let other = ToolList(vec![
+            Tool::new_binary("alacritty", "alacritty", "arch"),
+            Tool::new_binary("ripgrep", "rg", "arch"),
+        ]);
+
+        tools.merge(other);
+
+        let want = vec!["alacritty", "ripgrep"];
+        let got: Vec<&str> = tools.0.iter().map(|t| t.package.as_str()).collect();
+        assert_eq!(want, got);
+    }
+
     #[test]
     fn it_finds_tools_by_manager() {
         let tf = create_temp_json();